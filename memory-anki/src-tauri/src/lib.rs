@@ -1,13 +1,206 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, RunEvent, State};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// How long to wait after the last edit before writing storage to disk.
+const AUTOSAVE_DEBOUNCE: Duration = Duration::from_millis(750);
+
+const SECONDS_PER_DAY: i64 = 86_400;
+const MIN_EASE_FACTOR: f64 = 1.3;
+
+fn default_ease_factor() -> f64 {
+    2.5
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// A single spaced-repetition card, scheduled with the SM-2 algorithm.
+///
+/// Fields this struct doesn't declare (those that belong to the frontend, like
+/// the card's front/back content) round-trip untouched via `extra`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Card {
+    id: String,
+    deck: String,
+    #[serde(default = "default_ease_factor")]
+    ease_factor: f64,
+    #[serde(default)]
+    repetitions: u32,
+    #[serde(default)]
+    interval_days: u32,
+    #[serde(default)]
+    due: i64,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Applies one SM-2 review step to `card` in place.
+///
+/// `quality` is the recall grade in `0..=5`; `now` is the review's unix
+/// timestamp (seconds), from which the new `due` date is derived.
+fn apply_sm2(card: &mut Card, quality: u8, now: i64) -> Result<(), String> {
+    if quality > 5 {
+        return Err("quality must be between 0 and 5".to_string());
+    }
+    if quality >= 3 {
+        card.interval_days = if card.repetitions == 0 {
+            1
+        } else if card.repetitions == 1 {
+            6
+        } else {
+            (card.interval_days as f64 * card.ease_factor).round() as u32
+        };
+        card.repetitions += 1;
+    } else {
+        card.repetitions = 0;
+        card.interval_days = 1;
+    }
+    let q = f64::from(quality);
+    card.ease_factor =
+        (card.ease_factor + (0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02))).max(MIN_EASE_FACTOR);
+    card.due = now + card.interval_days as i64 * SECONDS_PER_DAY;
+    Ok(())
+}
+
+fn default_reminder_enabled() -> bool {
+    true
+}
+
+fn default_reminder_interval_secs() -> u64 {
+    300
+}
+
+/// Persisted settings for the background due-card reminder loop.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ReminderConfig {
+    #[serde(default = "default_reminder_enabled")]
+    enabled: bool,
+    #[serde(default = "default_reminder_interval_secs")]
+    interval_secs: u64,
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_reminder_enabled(),
+            interval_secs: default_reminder_interval_secs(),
+        }
+    }
+}
+
+/// Current on-disk schema version. Bump this and append a migration to
+/// `MIGRATIONS` whenever `StoragePayload` or `Card` gains/changes a field.
+const STORAGE_VERSION: u32 = 3;
+
+fn default_storage_version() -> u32 {
+    STORAGE_VERSION
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct StoragePayload {
-    cards: serde_json::Value,
+    #[serde(default = "default_storage_version")]
+    version: u32,
+    cards: Vec<Card>,
     decks: Vec<String>,
+    #[serde(default)]
+    reminder_config: ReminderConfig,
+}
+
+impl Default for StoragePayload {
+    fn default() -> Self {
+        Self {
+            version: STORAGE_VERSION,
+            cards: Vec::new(),
+            decks: Vec::new(),
+            reminder_config: ReminderConfig::default(),
+        }
+    }
+}
+
+type Migration = fn(serde_json::Value) -> serde_json::Value;
+
+/// v1 stored `cards` as a frontend-defined blob; v2 adds SM-2 scheduling
+/// fields, so give every existing card safe defaults instead of losing it.
+fn migrate_v1_to_v2(mut raw: serde_json::Value) -> serde_json::Value {
+    if let Some(cards) = raw.get_mut("cards").and_then(|cards| cards.as_array_mut()) {
+        for card in cards {
+            if let Some(card) = card.as_object_mut() {
+                card.entry("ease_factor").or_insert(serde_json::json!(2.5));
+                card.entry("repetitions").or_insert(serde_json::json!(0));
+                card.entry("interval_days").or_insert(serde_json::json!(0));
+                card.entry("due").or_insert(serde_json::json!(0));
+            }
+        }
+    }
+    raw
+}
+
+/// v3 adds a persisted reminder schedule; default it for older payloads.
+fn migrate_v2_to_v3(mut raw: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = raw.as_object_mut() {
+        object.entry("reminder_config").or_insert_with(|| {
+            serde_json::json!({
+                "enabled": default_reminder_enabled(),
+                "interval_secs": default_reminder_interval_secs(),
+            })
+        });
+    }
+    raw
+}
+
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Runs every migration between the stored version (missing or `0` => v1)
+/// and `STORAGE_VERSION` in order, then stamps the result with the new version.
+fn migrate_storage(raw: serde_json::Value) -> serde_json::Value {
+    let version = raw.get("version").and_then(|v| v.as_u64()).unwrap_or(1);
+    let mut version = if version == 0 { 1 } else { version } as u32;
+    let mut value = raw;
+    while (version as usize) <= MIGRATIONS.len() {
+        value = MIGRATIONS[version as usize - 1](value);
+        version += 1;
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), serde_json::json!(version));
+    }
+    value
+}
+
+/// In-memory copy of storage, managed as Tauri state so commands never have
+/// to re-read and re-parse the JSON file on every call.
+struct StorageState {
+    payload: Mutex<StoragePayload>,
+    dirty: AtomicBool,
+    /// Bumped on every edit; a pending debounce only writes if it's still current.
+    save_generation: AtomicU64,
+    /// Mirrors `payload.reminder_config` so the reminder loop can read it
+    /// every tick without taking the payload lock.
+    reminders_enabled: AtomicBool,
+    reminder_interval_secs: AtomicU64,
+}
+
+impl StorageState {
+    fn new(payload: StoragePayload) -> Self {
+        let reminder_config = payload.reminder_config.clone();
+        Self {
+            payload: Mutex::new(payload),
+            dirty: AtomicBool::new(false),
+            save_generation: AtomicU64::new(0),
+            reminders_enabled: AtomicBool::new(reminder_config.enabled),
+            reminder_interval_secs: AtomicU64::new(reminder_config.interval_secs.max(1)),
+        }
+    }
 }
 
 fn storage_path(app: &AppHandle) -> Result<PathBuf, String> {
@@ -19,28 +212,565 @@ fn storage_path(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(dir.join("memory-anki-storage.json"))
 }
 
-#[tauri::command]
-fn load_storage(app: AppHandle) -> Result<StoragePayload, String> {
-    let path = storage_path(&app)?;
+fn read_storage_file(path: &Path) -> Result<StoragePayload, String> {
     if !path.exists() {
         return Ok(StoragePayload::default());
     }
     let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
-    serde_json::from_str(&contents).map_err(|err| err.to_string())
+    let raw: serde_json::Value = serde_json::from_str(&contents).map_err(|err| err.to_string())?;
+    let original_version = raw.get("version").and_then(|v| v.as_u64()).unwrap_or(1);
+    let migrated = migrate_storage(raw);
+    let mut payload: StoragePayload =
+        serde_json::from_value(migrated).map_err(|err| err.to_string())?;
+    let normalized_interval = payload.reminder_config.interval_secs.max(1);
+    let needs_rewrite = original_version < STORAGE_VERSION as u64
+        || normalized_interval != payload.reminder_config.interval_secs;
+    payload.reminder_config.interval_secs = normalized_interval;
+    if needs_rewrite {
+        write_storage_file_atomic(path, &payload)?;
+    }
+    Ok(payload)
+}
+
+/// Writes storage to a sibling temp file and renames it over the real file,
+/// so a crash mid-write can never leave `memory-anki-storage.json` truncated.
+fn write_storage_file_atomic(path: &Path, payload: &StoragePayload) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(payload).map_err(|err| err.to_string())?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents).map_err(|err| err.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|err| err.to_string())
+}
+
+fn flush_storage_internal(app: &AppHandle, state: &StorageState) -> Result<(), String> {
+    if !state.dirty.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let path = storage_path(app)?;
+    let payload = state
+        .payload
+        .lock()
+        .map_err(|err| err.to_string())?
+        .clone();
+    write_storage_file_atomic(&path, &payload)
+}
+
+/// Schedules a debounced write, cancelling any earlier pending write so a
+/// burst of edits (e.g. bulk card editing) only hits disk once it settles.
+fn schedule_debounced_save(app: AppHandle) {
+    let state = app.state::<StorageState>();
+    let generation = state.save_generation.fetch_add(1, Ordering::SeqCst) + 1;
+    std::thread::spawn(move || {
+        std::thread::sleep(AUTOSAVE_DEBOUNCE);
+        let state = app.state::<StorageState>();
+        if state.save_generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if let Err(err) = flush_storage_internal(&app, &state) {
+            eprintln!("failed to autosave storage: {err}");
+        }
+    });
+}
+
+#[tauri::command]
+fn load_storage(state: State<'_, StorageState>) -> Result<StoragePayload, String> {
+    Ok(state.payload.lock().map_err(|err| err.to_string())?.clone())
+}
+
+/// Overwrites scheduling fields on `incoming` cards with whatever `previous`
+/// holds for the same id. Scheduling fields are scheduler-owned (only
+/// `review_card` may update them), so a `save_storage` built from a stale
+/// snapshot must not revert SM-2 progress applied since that snapshot was
+/// taken.
+fn preserve_scheduling_fields(incoming: &mut [Card], previous: &[Card]) {
+    let previous_by_id: HashMap<&str, &Card> = previous
+        .iter()
+        .map(|card| (card.id.as_str(), card))
+        .collect();
+    for card in incoming {
+        if let Some(previous) = previous_by_id.get(card.id.as_str()) {
+            card.ease_factor = previous.ease_factor;
+            card.repetitions = previous.repetitions;
+            card.interval_days = previous.interval_days;
+            card.due = previous.due;
+        }
+    }
+}
+
+#[tauri::command]
+fn save_storage(
+    app: AppHandle,
+    state: State<'_, StorageState>,
+    mut payload: StoragePayload,
+) -> Result<(), String> {
+    state
+        .reminders_enabled
+        .store(payload.reminder_config.enabled, Ordering::SeqCst);
+    state
+        .reminder_interval_secs
+        .store(payload.reminder_config.interval_secs.max(1), Ordering::SeqCst);
+
+    let mut current = state.payload.lock().map_err(|err| err.to_string())?;
+    preserve_scheduling_fields(&mut payload.cards, &current.cards);
+    *current = payload;
+    state.dirty.store(true, Ordering::SeqCst);
+    schedule_debounced_save(app);
+    Ok(())
+}
+
+#[tauri::command]
+fn flush_storage(app: AppHandle, state: State<'_, StorageState>) -> Result<(), String> {
+    flush_storage_internal(&app, &state)
+}
+
+#[tauri::command]
+fn review_card(
+    app: AppHandle,
+    state: State<'_, StorageState>,
+    card_id: String,
+    quality: u8,
+) -> Result<Card, String> {
+    let mut payload = state.payload.lock().map_err(|err| err.to_string())?;
+    let card = payload
+        .cards
+        .iter_mut()
+        .find(|card| card.id == card_id)
+        .ok_or_else(|| format!("no card with id `{card_id}`"))?;
+    apply_sm2(card, quality, now_unix())?;
+    let reviewed = card.clone();
+    drop(payload);
+
+    state.dirty.store(true, Ordering::SeqCst);
+    schedule_debounced_save(app);
+    Ok(reviewed)
+}
+
+/// Cards due by `now` (optionally restricted to `deck`), soonest due first.
+fn filter_due_cards(cards: &[Card], deck: Option<&str>, now: i64) -> Vec<Card> {
+    let mut due: Vec<Card> = cards
+        .iter()
+        .filter(|card| card.due <= now)
+        .filter(|card| deck.map_or(true, |deck| card.deck == deck))
+        .cloned()
+        .collect();
+    due.sort_by_key(|card| card.due);
+    due
+}
+
+#[tauri::command]
+fn due_cards(
+    state: State<'_, StorageState>,
+    deck: Option<String>,
+    now: i64,
+) -> Result<Vec<Card>, String> {
+    let payload = state.payload.lock().map_err(|err| err.to_string())?;
+    Ok(filter_due_cards(&payload.cards, deck.as_deref(), now))
+}
+
+fn media_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| err.to_string())?
+        .join("media");
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    Ok(dir)
+}
+
+/// Deterministic cache path for `url`: `<media_dir>/<md5(url)>.<ext>`, so the
+/// same URL always resolves to the same file without a lookup table.
+fn media_cache_path(dir: &Path, url: &str) -> PathBuf {
+    let digest = format!("{:x}", md5::compute(url.as_bytes()));
+    let ext = Path::new(url)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("bin");
+    dir.join(format!("{digest}.{ext}"))
+}
+
+/// Downloads `url` into the media cache (unless already cached) and returns
+/// its local path, URL-encoded so it can be handed straight to an `<img>`/
+/// `<audio>` tag served from `asset://localhost/`.
+#[tauri::command]
+async fn cache_media(app: AppHandle, url: String) -> Result<String, String> {
+    let dir = media_dir(&app)?;
+    let path = media_cache_path(&dir, &url);
+    if !path.exists() {
+        let response = reqwest::get(&url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| err.to_string())?;
+        let bytes = response.bytes().await.map_err(|err| err.to_string())?;
+        fs::write(&path, &bytes).map_err(|err| err.to_string())?;
+    }
+    Ok(urlencoding::encode(&path.to_string_lossy()).into_owned())
+}
+
+#[tauri::command]
+fn clear_media_cache(app: AppHandle) -> Result<(), String> {
+    let dir = media_dir(&app)?;
+    fs::remove_dir_all(&dir).map_err(|err| err.to_string())?;
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())
+}
+
+/// Deletes cached media files no stored card references any more. Returns
+/// the number of files removed. A card references a URL only when one of its
+/// top-level `extra` string fields is exactly that URL; URLs embedded in
+/// longer text or in nested values are not counted, so their files are removed.
+#[tauri::command]
+fn prune_media(app: AppHandle, state: State<'_, StorageState>) -> Result<u32, String> {
+    let dir = media_dir(&app)?;
+    let payload = state.payload.lock().map_err(|err| err.to_string())?;
+    let referenced: HashSet<PathBuf> = payload
+        .cards
+        .iter()
+        .flat_map(|card| card.extra.values())
+        .filter_map(|value| value.as_str())
+        .map(|url| media_cache_path(&dir, url))
+        .collect();
+    drop(payload);
+
+    let mut pruned = 0u32;
+    for entry in fs::read_dir(&dir).map_err(|err| err.to_string())? {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if !referenced.contains(&path) {
+            fs::remove_file(&path).map_err(|err| err.to_string())?;
+            pruned += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+/// Number of cards past due, per deck, as of `now`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+struct DeckDueCount {
+    deck: String,
+    count: u32,
+}
+
+/// Number of due cards per deck, sorted by deck name.
+fn count_due_by_deck(cards: &[Card], now: i64) -> Vec<DeckDueCount> {
+    let mut counts: BTreeMap<String, u32> = BTreeMap::new();
+    for card in cards.iter().filter(|card| card.due <= now) {
+        *counts.entry(card.deck.clone()).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(deck, count)| DeckDueCount { deck, count })
+        .collect()
+}
+
+fn due_summary(state: &StorageState, now: i64) -> Vec<DeckDueCount> {
+    let Ok(payload) = state.payload.lock() else {
+        return Vec::new();
+    };
+    count_due_by_deck(&payload.cards, now)
+}
+
+/// How often the reminder loop re-checks the configured interval while
+/// waiting, so a `set_reminder_config` call that shortens it takes effect
+/// promptly instead of only after the previous, longer interval elapses.
+const REMINDER_POLL_TICK: Duration = Duration::from_secs(1);
+
+/// Background loop (spawned once from `.setup`) that periodically emits a
+/// `cards-due` event so the frontend can surface reminders without polling.
+/// It fires immediately on startup, then again every configured interval.
+fn spawn_due_reminder_loop(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut elapsed = Duration::ZERO;
+        let mut due = true;
+        loop {
+            let state = app.state::<StorageState>();
+            if due {
+                if state.reminders_enabled.load(Ordering::SeqCst) {
+                    let summary = due_summary(&state, now_unix());
+                    if let Err(err) = app.emit("cards-due", &summary) {
+                        eprintln!("failed to emit cards-due event: {err}");
+                    }
+                }
+                elapsed = Duration::ZERO;
+            }
+
+            let interval_secs = state.reminder_interval_secs.load(Ordering::SeqCst).max(1);
+            let interval = Duration::from_secs(interval_secs);
+            let tick = REMINDER_POLL_TICK.min(interval);
+            std::thread::sleep(tick);
+            elapsed += tick;
+            due = elapsed >= interval;
+        }
+    });
 }
 
 #[tauri::command]
-fn save_storage(app: AppHandle, payload: StoragePayload) -> Result<(), String> {
-    let path = storage_path(&app)?;
-    let contents = serde_json::to_string_pretty(&payload).map_err(|err| err.to_string())?;
-    fs::write(path, contents).map_err(|err| err.to_string())
+fn get_reminder_config(state: State<'_, StorageState>) -> Result<ReminderConfig, String> {
+    Ok(state
+        .payload
+        .lock()
+        .map_err(|err| err.to_string())?
+        .reminder_config
+        .clone())
+}
+
+#[tauri::command]
+fn set_reminder_config(
+    app: AppHandle,
+    state: State<'_, StorageState>,
+    mut config: ReminderConfig,
+) -> Result<(), String> {
+    config.interval_secs = config.interval_secs.max(1);
+    state
+        .reminders_enabled
+        .store(config.enabled, Ordering::SeqCst);
+    state
+        .reminder_interval_secs
+        .store(config.interval_secs, Ordering::SeqCst);
+    state.payload.lock().map_err(|err| err.to_string())?.reminder_config = config;
+    state.dirty.store(true, Ordering::SeqCst);
+    schedule_debounced_save(app);
+    Ok(())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![load_storage, save_storage])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(tauri::generate_handler![
+            load_storage,
+            save_storage,
+            flush_storage,
+            review_card,
+            due_cards,
+            cache_media,
+            clear_media_cache,
+            prune_media,
+            get_reminder_config,
+            set_reminder_config
+        ])
+        .setup(|app| {
+            let handle = app.handle().clone();
+            let path = storage_path(&handle)?;
+            let payload = read_storage_file(&path)?;
+            app.manage(StorageState::new(payload));
+            spawn_due_reminder_loop(handle);
+            Ok(())
+        })
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            // Make sure a debounced write still in flight isn't lost on quit.
+            if let RunEvent::ExitRequested { .. } = event {
+                let state = app_handle.state::<StorageState>();
+                if let Err(err) = flush_storage_internal(app_handle, &state) {
+                    eprintln!("failed to flush storage on exit: {err}");
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_card(id: &str, deck: &str) -> Card {
+        Card {
+            id: id.to_string(),
+            deck: deck.to_string(),
+            ease_factor: default_ease_factor(),
+            repetitions: 0,
+            interval_days: 0,
+            due: 0,
+            extra: serde_json::Map::new(),
+        }
+    }
+
+    #[test]
+    fn apply_sm2_first_good_review_sets_interval_to_one_day() {
+        let mut card = new_card("1", "spanish");
+        apply_sm2(&mut card, 4, 0).unwrap();
+        assert_eq!(card.repetitions, 1);
+        assert_eq!(card.interval_days, 1);
+        assert_eq!(card.due, SECONDS_PER_DAY);
+        assert_eq!(card.ease_factor, 2.5);
+    }
+
+    #[test]
+    fn apply_sm2_second_good_review_sets_interval_to_six_days() {
+        let mut card = new_card("1", "spanish");
+        apply_sm2(&mut card, 4, 0).unwrap();
+        apply_sm2(&mut card, 4, SECONDS_PER_DAY).unwrap();
+        assert_eq!(card.repetitions, 2);
+        assert_eq!(card.interval_days, 6);
+    }
+
+    #[test]
+    fn apply_sm2_third_good_review_multiplies_interval_by_ease_factor() {
+        let mut card = new_card("1", "spanish");
+        apply_sm2(&mut card, 5, 0).unwrap();
+        apply_sm2(&mut card, 5, 0).unwrap();
+        let ease_factor = card.ease_factor;
+        let interval_before = card.interval_days;
+        apply_sm2(&mut card, 5, 0).unwrap();
+        assert_eq!(card.repetitions, 3);
+        assert_eq!(
+            card.interval_days,
+            (interval_before as f64 * ease_factor).round() as u32
+        );
+    }
+
+    #[test]
+    fn apply_sm2_failing_review_resets_progress() {
+        let mut card = new_card("1", "spanish");
+        apply_sm2(&mut card, 5, 0).unwrap();
+        apply_sm2(&mut card, 5, 0).unwrap();
+        apply_sm2(&mut card, 1, 0).unwrap();
+        assert_eq!(card.repetitions, 0);
+        assert_eq!(card.interval_days, 1);
+    }
+
+    #[test]
+    fn apply_sm2_clamps_ease_factor_to_minimum() {
+        let mut card = new_card("1", "spanish");
+        for _ in 0..20 {
+            apply_sm2(&mut card, 0, 0).unwrap();
+        }
+        assert_eq!(card.ease_factor, MIN_EASE_FACTOR);
+    }
+
+    #[test]
+    fn apply_sm2_rejects_out_of_range_quality() {
+        let mut card = new_card("1", "spanish");
+        assert!(apply_sm2(&mut card, 6, 0).is_err());
+    }
+
+    #[test]
+    fn filter_due_cards_sorts_by_due_date_and_respects_deck_filter() {
+        let mut spanish_a = new_card("a", "spanish");
+        spanish_a.due = 200;
+        let mut spanish_b = new_card("b", "spanish");
+        spanish_b.due = 100;
+        let mut french_c = new_card("c", "french");
+        french_c.due = 50;
+        let cards = vec![spanish_a, spanish_b, french_c];
+
+        let all_due = filter_due_cards(&cards, None, 200);
+        assert_eq!(
+            all_due.iter().map(|card| card.id.as_str()).collect::<Vec<_>>(),
+            vec!["c", "b", "a"]
+        );
+
+        let spanish_due = filter_due_cards(&cards, Some("spanish"), 200);
+        assert_eq!(
+            spanish_due
+                .iter()
+                .map(|card| card.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b", "a"]
+        );
+
+        let not_yet_due = filter_due_cards(&cards, None, 60);
+        assert_eq!(not_yet_due.len(), 1);
+        assert_eq!(not_yet_due[0].id, "c");
+    }
+
+    #[test]
+    fn count_due_by_deck_groups_and_counts_per_deck() {
+        let mut spanish_a = new_card("a", "spanish");
+        spanish_a.due = 0;
+        let mut spanish_b = new_card("b", "spanish");
+        spanish_b.due = 0;
+        let mut french_c = new_card("c", "french");
+        french_c.due = 0;
+        let mut french_d = new_card("d", "french");
+        french_d.due = 1_000;
+        let cards = vec![spanish_a, spanish_b, french_c, french_d];
+
+        let summary = count_due_by_deck(&cards, 0);
+        assert_eq!(
+            summary,
+            vec![
+                DeckDueCount {
+                    deck: "french".to_string(),
+                    count: 1
+                },
+                DeckDueCount {
+                    deck: "spanish".to_string(),
+                    count: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn migrate_storage_treats_missing_version_as_v1() {
+        let raw = serde_json::json!({
+            "cards": [{"id": "1", "deck": "x"}],
+            "decks": ["x"],
+        });
+        let migrated = migrate_storage(raw);
+        assert_eq!(migrated["version"], serde_json::json!(STORAGE_VERSION));
+        assert_eq!(migrated["cards"][0]["ease_factor"], serde_json::json!(2.5));
+    }
+
+    #[test]
+    fn migrate_storage_treats_version_zero_as_v1_without_underflowing() {
+        let raw = serde_json::json!({ "version": 0, "cards": [], "decks": [] });
+        let migrated = migrate_storage(raw);
+        assert_eq!(migrated["version"], serde_json::json!(STORAGE_VERSION));
+    }
+
+    #[test]
+    fn migrate_storage_adds_default_reminder_config_for_v2_payloads() {
+        let raw = serde_json::json!({ "version": 2, "cards": [], "decks": [] });
+        let migrated = migrate_storage(raw);
+        assert_eq!(migrated["version"], serde_json::json!(STORAGE_VERSION));
+        assert_eq!(
+            migrated["reminder_config"]["interval_secs"],
+            serde_json::json!(default_reminder_interval_secs())
+        );
+    }
+
+    #[test]
+    fn migrate_storage_is_a_no_op_at_the_current_version() {
+        let raw = serde_json::json!({
+            "version": STORAGE_VERSION,
+            "cards": [],
+            "decks": [],
+            "reminder_config": {"enabled": false, "interval_secs": 42},
+        });
+        let migrated = migrate_storage(raw);
+        assert_eq!(
+            migrated["reminder_config"]["interval_secs"],
+            serde_json::json!(42)
+        );
+    }
+
+    #[test]
+    fn preserve_scheduling_fields_keeps_previous_sm2_progress() {
+        let mut previous_card = new_card("1", "spanish");
+        previous_card.ease_factor = 2.1;
+        previous_card.repetitions = 3;
+        previous_card.interval_days = 6;
+        previous_card.due = 12_345;
+        let previous = vec![previous_card];
+
+        // A stale snapshot still carrying the pre-review scheduling fields.
+        let mut incoming = vec![new_card("1", "spanish")];
+        preserve_scheduling_fields(&mut incoming, &previous);
+
+        assert_eq!(incoming[0].ease_factor, 2.1);
+        assert_eq!(incoming[0].repetitions, 3);
+        assert_eq!(incoming[0].interval_days, 6);
+        assert_eq!(incoming[0].due, 12_345);
+    }
+
+    #[test]
+    fn preserve_scheduling_fields_leaves_new_cards_untouched() {
+        let previous = vec![new_card("1", "spanish")];
+        let mut incoming = vec![new_card("2", "spanish")];
+        incoming[0].ease_factor = 1.8;
+
+        preserve_scheduling_fields(&mut incoming, &previous);
+
+        assert_eq!(incoming[0].ease_factor, 1.8);
+    }
 }